@@ -0,0 +1,98 @@
+// src/account_id.rs
+//
+// Canonical ICP account identifiers: a SHA-224 digest of a principal and
+// subaccount, prefixed with a CRC32 checksum of that digest, hex-encoded.
+// This lets `add_or_update_user` reject garbage `account_id` values instead
+// of storing whatever string a caller happens to send.
+
+use crate::AccountId;
+use ic_cdk::export::candid::Principal;
+use sha2::{Digest, Sha224};
+
+const ACCOUNT_DOMAIN_SEPARATOR: &[u8] = b"\x0Aaccount-id";
+
+/// Derive the canonical 32-byte (64 hex char) account identifier for a
+/// principal and subaccount.
+pub(crate) fn account_id_from_principal(principal: &Principal, subaccount: [u8; 32]) -> AccountId {
+    let mut hasher = Sha224::new();
+    hasher.update(ACCOUNT_DOMAIN_SEPARATOR);
+    hasher.update(principal.as_slice());
+    hasher.update(subaccount);
+    let hash = hasher.finalize();
+
+    let mut bytes = Vec::with_capacity(32);
+    bytes.extend_from_slice(&crc32fast::hash(&hash).to_be_bytes());
+    bytes.extend_from_slice(&hash);
+
+    hex::encode(bytes)
+}
+
+/// Check that `account_id` is well-formed: 32 bytes of hex whose leading 4
+/// bytes are the CRC32 checksum of the trailing 28.
+pub(crate) fn validate_account_id(account_id: &AccountId) -> Result<(), String> {
+    let bytes = hex::decode(account_id).map_err(|e| format!("account_id is not valid hex: {}", e))?;
+    if bytes.len() != 32 {
+        return Err(format!(
+            "account_id must decode to 32 bytes, got {}",
+            bytes.len()
+        ));
+    }
+
+    let (checksum, hash) = bytes.split_at(4);
+    if checksum != crc32fast::hash(hash).to_be_bytes() {
+        return Err("account_id checksum does not match its hash".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_known_account_id_for_anonymous_principal() {
+        let account_id = account_id_from_principal(&Principal::anonymous(), [0u8; 32]);
+
+        assert_eq!(
+            account_id,
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79"
+        );
+    }
+
+    #[test]
+    fn derives_known_account_id_for_management_canister_principal() {
+        let principal = Principal::from_text("aaaaa-aa").unwrap();
+        let account_id = account_id_from_principal(&principal, [0u8; 32]);
+
+        assert_eq!(
+            account_id,
+            "2d0e897f7e862d2b57d9bc9ea5c65f9a24ac6c074575f47898314b8d6cb0929d"
+        );
+    }
+
+    #[test]
+    fn validates_a_well_formed_account_id() {
+        let account_id = account_id_from_principal(&Principal::anonymous(), [0u8; 32]);
+
+        assert!(validate_account_id(&account_id).is_ok());
+    }
+
+    #[test]
+    fn rejects_an_account_id_with_a_corrupted_checksum() {
+        let mut account_id = account_id_from_principal(&Principal::anonymous(), [0u8; 32]);
+        account_id.replace_range(0..2, "ff");
+
+        assert_eq!(
+            validate_account_id(&account_id).unwrap_err(),
+            "account_id checksum does not match its hash"
+        );
+    }
+
+    #[test]
+    fn rejects_non_hex_input() {
+        let result = validate_account_id(&"not hex".to_string());
+
+        assert!(result.unwrap_err().contains("not valid hex"));
+    }
+}
@@ -0,0 +1,119 @@
+// src/cleanup.rs
+//
+// A recurring timer that sweeps `STATE.users` for stale entries, so memory
+// stays bounded without an operator having to prune the map by hand.
+
+use crate::{CleanupPolicy, PrincipalText, User, STATE};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Start the recurring sweep. Safe to call more than once per canister
+/// lifetime (e.g. from both `init` and `post_upgrade`) since each call just
+/// schedules another independent timer.
+pub(crate) fn start_cleanup_timer() {
+    ic_cdk_timers::set_timer_interval(CLEANUP_INTERVAL, run_cleanup);
+}
+
+/// Principals eligible for eviction under `policy` as of `now_ns`: a
+/// zero balance and a `last_seen_ns` older than `policy.ttl_ns`. Pure
+/// function over `users` — no canister APIs — so it's unit testable
+/// without a running canister.
+fn stale_principals(
+    users: &HashMap<PrincipalText, User>,
+    now_ns: u64,
+    policy: CleanupPolicy,
+) -> Vec<PrincipalText> {
+    if policy.keep_zero_balance {
+        return Vec::new();
+    }
+
+    users
+        .iter()
+        .filter(|(_, user)| {
+            let stale = now_ns.saturating_sub(user.last_seen_ns) > policy.ttl_ns;
+            stale && user.balance_e8s == 0
+        })
+        .map(|(principal, _)| principal.clone())
+        .collect()
+}
+
+/// Remove zero-balance users whose `last_seen_ns` is older than the
+/// configured TTL, unless `keep_zero_balance` opts out of the sweep entirely.
+/// Evicted principals are also dropped from `balance_index`, otherwise the
+/// sweep just moves the unbounded growth it's meant to fix into that map.
+fn run_cleanup() {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let now_ns = ic_cdk::api::time();
+        let stale = stale_principals(&state.users, now_ns, state.cleanup_policy);
+
+        for principal in stale {
+            state.users.remove(&principal);
+            state.remove_from_index(&principal, 0);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Balance;
+
+    fn user(balance_e8s: Balance, last_seen_ns: u64) -> User {
+        User {
+            principal_id: "irrelevant".to_string(),
+            account_id: String::new(),
+            balance_e8s,
+            last_seen_ns,
+        }
+    }
+
+    fn policy(ttl_ns: u64, keep_zero_balance: bool) -> CleanupPolicy {
+        CleanupPolicy {
+            ttl_ns,
+            keep_zero_balance,
+        }
+    }
+
+    #[test]
+    fn evicts_stale_zero_balance_users() {
+        let mut users = HashMap::new();
+        users.insert("stale".to_string(), user(0, 0));
+
+        let stale = stale_principals(&users, 100, policy(10, false));
+
+        assert_eq!(stale, vec!["stale".to_string()]);
+    }
+
+    #[test]
+    fn keeps_users_seen_within_the_ttl() {
+        let mut users = HashMap::new();
+        users.insert("fresh".to_string(), user(0, 95));
+
+        let stale = stale_principals(&users, 100, policy(10, false));
+
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn keeps_stale_users_with_a_nonzero_balance() {
+        let mut users = HashMap::new();
+        users.insert("holder".to_string(), user(1, 0));
+
+        let stale = stale_principals(&users, 100, policy(10, false));
+
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn keep_zero_balance_opts_out_of_the_sweep_entirely() {
+        let mut users = HashMap::new();
+        users.insert("stale".to_string(), user(0, 0));
+
+        let stale = stale_principals(&users, 100, policy(10, true));
+
+        assert!(stale.is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+// src/icrc1.rs
+//
+// Minimal ICRC-1-shaped surface over `BackendState.users`: balances stop
+// being a passive number and become something that can actually move
+// between principals.
+
+use crate::{Balance, PrincipalText, User, STATE};
+use ic_cdk::export::candid::Principal;
+use ic_cdk_macros::{query, update};
+use std::collections::HashMap;
+
+/// The balance changes a transfer produced, so the caller can update
+/// `balance_index` without `apply_transfer` needing to know about it.
+#[derive(Debug)]
+struct TransferEffect {
+    from_old_balance: Balance,
+    from_new_balance: Balance,
+    to_old_balance: Option<Balance>,
+    to_new_balance: Balance,
+}
+
+/// Debit `from` and credit `to` by `amount_e8s`, creating `to`'s `User`
+/// record if it doesn't exist. Pure function over `users` — no canister
+/// APIs — so the arithmetic can be unit tested without a running canister.
+///
+/// Both the underflow and overflow checks happen before either balance is
+/// written, so a rejected transfer leaves `users` completely untouched.
+fn apply_transfer(
+    users: &mut HashMap<PrincipalText, User>,
+    from: &PrincipalText,
+    to: &PrincipalText,
+    amount_e8s: Balance,
+    now_ns: u64,
+) -> Result<TransferEffect, String> {
+    let from_old_balance = users
+        .get(from)
+        .map(|user| user.balance_e8s)
+        .ok_or_else(|| format!("no account for caller {}", from))?;
+    let from_new_balance = from_old_balance
+        .checked_sub(amount_e8s)
+        .ok_or_else(|| "insufficient balance".to_string())?;
+
+    // A self-transfer nets out to "balance unchanged", so short-circuit it
+    // instead of letting the debit and credit below clobber each other.
+    if from == to {
+        users.get_mut(from).unwrap().last_seen_ns = now_ns;
+        return Ok(TransferEffect {
+            from_old_balance,
+            from_new_balance: from_old_balance,
+            to_old_balance: Some(from_old_balance),
+            to_new_balance: from_old_balance,
+        });
+    }
+
+    let to_old_balance = users.get(to).map(|user| user.balance_e8s);
+    let to_new_balance = to_old_balance
+        .unwrap_or(0)
+        .checked_add(amount_e8s)
+        .ok_or_else(|| "recipient balance overflow".to_string())?;
+
+    let sender = users.get_mut(from).unwrap();
+    sender.balance_e8s = from_new_balance;
+    sender.last_seen_ns = now_ns;
+
+    let recipient = users.entry(to.clone()).or_insert_with(|| User {
+        principal_id: to.clone(),
+        account_id: String::new(),
+        balance_e8s: 0,
+        last_seen_ns: now_ns,
+    });
+    recipient.balance_e8s = to_new_balance;
+    recipient.last_seen_ns = now_ns;
+
+    Ok(TransferEffect {
+        from_old_balance,
+        from_new_balance,
+        to_old_balance,
+        to_new_balance,
+    })
+}
+
+/// Move `amount_e8s` from the caller's balance to `to`, creating the
+/// recipient's `User` record if it doesn't exist yet. Debit and credit
+/// happen inside a single `STATE.with` borrow so the transfer is atomic.
+///
+/// `to` is validated as a well-formed principal first, the same as
+/// `add_or_update_user`'s `principal_id` — otherwise the balance would be
+/// credited to a string nobody can ever authenticate as and call from.
+#[update]
+fn transfer(to: PrincipalText, amount_e8s: Balance) -> Result<Balance, String> {
+    Principal::from_text(&to).map_err(|e| format!("invalid to principal: {}", e))?;
+
+    let from = ic_cdk::caller().to_text();
+    let now_ns = ic_cdk::api::time();
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let effect = apply_transfer(&mut state.users, &from, &to, amount_e8s, now_ns)?;
+        state.reindex_balance(&from, Some(effect.from_old_balance), effect.from_new_balance);
+        state.reindex_balance(&to, effect.to_old_balance, effect.to_new_balance);
+        Ok(effect.from_new_balance)
+    })
+}
+
+/// The balance of `principal`, or `0` if it has no account.
+#[query]
+fn icrc1_balance_of(principal: PrincipalText) -> Balance {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .users
+            .get(&principal)
+            .map(|user| user.balance_e8s)
+            .unwrap_or(0)
+    })
+}
+
+/// Sum of every account's balance.
+#[query]
+fn icrc1_total_supply() -> Balance {
+    STATE.with(|state| state.borrow().users.values().map(|user| user.balance_e8s).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(principal_id: &str, balance_e8s: Balance) -> User {
+        User {
+            principal_id: principal_id.to_string(),
+            account_id: String::new(),
+            balance_e8s,
+            last_seen_ns: 0,
+        }
+    }
+
+    #[test]
+    fn debits_sender_and_credits_existing_recipient() {
+        let mut users = HashMap::new();
+        users.insert("alice".to_string(), user("alice", 100));
+        users.insert("bob".to_string(), user("bob", 10));
+
+        let effect = apply_transfer(&mut users, &"alice".to_string(), &"bob".to_string(), 40, 1).unwrap();
+
+        assert_eq!(effect.from_new_balance, 60);
+        assert_eq!(effect.to_new_balance, 50);
+        assert_eq!(users["alice"].balance_e8s, 60);
+        assert_eq!(users["bob"].balance_e8s, 50);
+    }
+
+    #[test]
+    fn creates_recipient_if_absent() {
+        let mut users = HashMap::new();
+        users.insert("alice".to_string(), user("alice", 100));
+
+        let effect = apply_transfer(&mut users, &"alice".to_string(), &"bob".to_string(), 40, 1).unwrap();
+
+        assert_eq!(effect.to_old_balance, None);
+        assert_eq!(effect.to_new_balance, 40);
+        assert_eq!(users["bob"].balance_e8s, 40);
+    }
+
+    #[test]
+    fn rejects_underflow_without_mutating_anything() {
+        let mut users = HashMap::new();
+        users.insert("alice".to_string(), user("alice", 10));
+
+        let result = apply_transfer(&mut users, &"alice".to_string(), &"bob".to_string(), 40, 1);
+
+        assert_eq!(result.unwrap_err(), "insufficient balance");
+        assert_eq!(users["alice"].balance_e8s, 10);
+        assert!(!users.contains_key("bob"));
+    }
+
+    #[test]
+    fn rejects_overflow_without_mutating_anything() {
+        let mut users = HashMap::new();
+        users.insert("alice".to_string(), user("alice", Balance::MAX));
+        users.insert("bob".to_string(), user("bob", 1));
+
+        let result = apply_transfer(&mut users, &"alice".to_string(), &"bob".to_string(), Balance::MAX, 1);
+
+        assert_eq!(result.unwrap_err(), "recipient balance overflow");
+        assert_eq!(users["alice"].balance_e8s, Balance::MAX);
+        assert_eq!(users["bob"].balance_e8s, 1);
+    }
+
+    #[test]
+    fn rejects_transfer_from_unknown_caller() {
+        let mut users = HashMap::new();
+
+        let result = apply_transfer(&mut users, &"ghost".to_string(), &"bob".to_string(), 1, 1);
+
+        assert_eq!(result.unwrap_err(), "no account for caller ghost");
+    }
+
+    #[test]
+    fn self_transfer_leaves_balance_unchanged() {
+        let mut users = HashMap::new();
+        users.insert("alice".to_string(), user("alice", 100));
+
+        let effect = apply_transfer(&mut users, &"alice".to_string(), &"alice".to_string(), 30, 1).unwrap();
+
+        assert_eq!(effect.from_new_balance, 100);
+        assert_eq!(effect.to_new_balance, 100);
+        assert_eq!(users["alice"].balance_e8s, 100);
+    }
+}
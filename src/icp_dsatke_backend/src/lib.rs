@@ -1,51 +1,223 @@
 // src/lib.rs
 
-use ic_cdk::export::candid::{CandidType, Deserialize};
-use ic_cdk_macros::{update, query};
+mod account_id;
+mod cleanup;
+mod icrc1;
+mod pagination;
+mod xdr_rate;
+
+use ic_cdk::export::candid::{CandidType, Deserialize, Principal};
+use ic_cdk_macros::{init, post_upgrade, pre_upgrade, query, update};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+pub(crate) type PrincipalText = String;
+pub(crate) type AccountId = String;
+pub(crate) type Balance = u64;
 
-type PrincipalText = String;
-type AccountId = String;
-type Balance = u64;
+/// Bump this whenever `User`'s on-wire layout changes, and add a case to
+/// `migrate_users` that maps the previous version's records into the new one.
+const SCHEMA_VERSION: u32 = 1;
 
 #[derive(Clone, CandidType, Deserialize, Debug)]
-struct User {
-    principal_id: PrincipalText,
-    account_id: AccountId,
-    balance_e8s: Balance,
+pub(crate) struct UserV1 {
+    pub(crate) principal_id: PrincipalText,
+    pub(crate) account_id: AccountId,
+    pub(crate) balance_e8s: Balance,
+    /// Nanosecond timestamp (`ic_cdk::api::time()`) of the last
+    /// `add_or_update_user` call or `transfer` (as sender or recipient)
+    /// for this principal. Used by the cleanup
+    /// timer to find entries that have gone stale.
+    pub(crate) last_seen_ns: u64,
+}
+
+pub(crate) type User = UserV1;
+
+/// Retention rule applied by the periodic cleanup timer, see [`cleanup`].
+#[derive(Clone, Copy, CandidType, Deserialize)]
+pub(crate) struct CleanupPolicy {
+    pub(crate) ttl_ns: u64,
+    pub(crate) keep_zero_balance: bool,
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        // 30 days, and don't delete zero-balance accounts unless asked to.
+        CleanupPolicy {
+            ttl_ns: 30 * 24 * 60 * 60 * 1_000_000_000,
+            keep_zero_balance: true,
+        }
+    }
 }
 
 #[derive(Default)]
-struct BackendState {
-    users: HashMap<PrincipalText, User>,
+pub(crate) struct BackendState {
+    pub(crate) users: HashMap<PrincipalText, User>,
+    pub(crate) cleanup_policy: CleanupPolicy,
+    /// Secondary index from balance to the principals holding it, kept in
+    /// sync with `users` so `get_top_holders` doesn't have to scan the map.
+    pub(crate) balance_index: BTreeMap<Balance, Vec<PrincipalText>>,
+    pub(crate) xdr_rate: xdr_rate::RateCache,
+}
+
+impl BackendState {
+    /// Move `principal` from its old balance bucket (if any) to its new
+    /// one. Call this every time a balance in `users` changes.
+    pub(crate) fn reindex_balance(
+        &mut self,
+        principal: &PrincipalText,
+        old_balance: Option<Balance>,
+        new_balance: Balance,
+    ) {
+        if let Some(old_balance) = old_balance {
+            self.remove_from_index(principal, old_balance);
+        }
+        self.balance_index
+            .entry(new_balance)
+            .or_default()
+            .push(principal.clone());
+    }
+
+    /// Drop `principal` out of its `balance` bucket without reinserting it
+    /// anywhere. Call this when a user is removed from `users` entirely
+    /// (e.g. by the cleanup sweep), as opposed to `reindex_balance`, which
+    /// is for balance changes on a user that still exists.
+    pub(crate) fn remove_from_index(&mut self, principal: &PrincipalText, balance: Balance) {
+        if let Some(bucket) = self.balance_index.get_mut(&balance) {
+            bucket.retain(|p| p != principal);
+            if bucket.is_empty() {
+                self.balance_index.remove(&balance);
+            }
+        }
+    }
+
+    /// Rebuild `balance_index` from scratch, e.g. after restoring `users`
+    /// from stable memory (the index itself isn't persisted).
+    pub(crate) fn rebuild_balance_index(&mut self) {
+        self.balance_index.clear();
+        for user in self.users.values() {
+            self.balance_index
+                .entry(user.balance_e8s)
+                .or_default()
+                .push(user.principal_id.clone());
+        }
+    }
+}
+
+/// What actually gets written to stable memory: the schema version travels
+/// alongside the data so `post_upgrade` can tell an old layout from the
+/// current one and migrate instead of failing to decode.
+#[derive(CandidType, Deserialize)]
+struct StableState {
+    version: u32,
+    users: HashMap<PrincipalText, UserV1>,
+    cleanup_policy: CleanupPolicy,
+    xdr_rate: xdr_rate::RateCache,
+}
+
+/// Map records from whatever `version` was persisted into the current
+/// `User` shape. There is only one schema so far, so this is the identity;
+/// future versions add a match arm here instead of a new `UsersN` store.
+fn migrate_users(version: u32, users: HashMap<PrincipalText, UserV1>) -> HashMap<PrincipalText, User> {
+    match version {
+        SCHEMA_VERSION => users,
+        _ => users,
+    }
 }
 
 // Thread-safe storage for canister state
 thread_local! {
-    static STATE: RefCell<BackendState> = RefCell::new(BackendState::default());
+    pub(crate) static STATE: RefCell<BackendState> = RefCell::new(BackendState::default());
 }
 
-/// Add or update a user — this changes state, so it's an `update` call
+/// Add or update a user — this changes state, so it's an `update` call.
+///
+/// An empty `account_id` is derived from `principal_id`; a non-empty one is
+/// verified against its own CRC32 checksum so callers can't store garbage.
 #[update]
-fn add_or_update_user(principal_id: String, account_id: String, balance_e8s: Balance) -> String {
+fn add_or_update_user(principal_id: String, account_id: String, balance_e8s: Balance) -> Result<String, String> {
+    let principal = Principal::from_text(&principal_id)
+        .map_err(|e| format!("invalid principal_id: {}", e))?;
+
+    let account_id = if account_id.is_empty() {
+        account_id::account_id_from_principal(&principal, [0u8; 32])
+    } else {
+        account_id::validate_account_id(&account_id)?;
+        account_id
+    };
+
     let user = User {
         principal_id: principal_id.clone(),
         account_id,
         balance_e8s,
+        last_seen_ns: ic_cdk::api::time(),
     };
 
     STATE.with(|state| {
-        state.borrow_mut().users.insert(principal_id.clone(), user);
+        let mut state = state.borrow_mut();
+        let old_balance = state.users.get(&principal_id).map(|u| u.balance_e8s);
+        state.users.insert(principal_id.clone(), user);
+        state.reindex_balance(&principal_id, old_balance, balance_e8s);
     });
 
-    format!("User {} stored successfully", principal_id)
+    Ok(format!("User {} stored successfully", principal_id))
+}
+
+/// Replace the cleanup timer's retention policy.
+#[update]
+fn set_cleanup_policy(ttl_ns: u64, keep_zero_balance: bool) {
+    STATE.with(|state| {
+        state.borrow_mut().cleanup_policy = CleanupPolicy {
+            ttl_ns,
+            keep_zero_balance,
+        };
+    });
 }
 
-/// Return all users — reads state, so it's a `query` call
+/// Report the schema version of the currently running canister, so clients
+/// can detect a mismatch against data they cached from a previous version.
 #[query]
-fn get_all_users() -> Vec<User> {
+fn schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Start the recurring cleanup timer on first install.
+#[init]
+fn init() {
+    cleanup::start_cleanup_timer();
+}
+
+/// Snapshot `users` into stable memory before the canister is upgraded,
+/// otherwise `dfx canister install --mode upgrade` wipes all state.
+#[pre_upgrade]
+fn pre_upgrade() {
+    let (users, cleanup_policy, xdr_rate) = STATE.with(|state| {
+        let state = state.borrow();
+        (state.users.clone(), state.cleanup_policy, state.xdr_rate)
+    });
+    let stable_state = StableState {
+        version: SCHEMA_VERSION,
+        users,
+        cleanup_policy,
+        xdr_rate,
+    };
+    ic_cdk::storage::stable_save((stable_state,)).expect("failed to save state to stable memory");
+}
+
+/// Restore `users` from stable memory after an upgrade, migrating them if
+/// the persisted `version` predates the one this build expects, and
+/// restart the cleanup timer (timers don't survive an upgrade).
+#[post_upgrade]
+fn post_upgrade() {
+    let (stable_state,): (StableState,) =
+        ic_cdk::storage::stable_restore().expect("failed to restore state from stable memory");
+    let users = migrate_users(stable_state.version, stable_state.users);
     STATE.with(|state| {
-        state.borrow().users.values().cloned().collect()
-    })
+        let mut state = state.borrow_mut();
+        state.users = users;
+        state.cleanup_policy = stable_state.cleanup_policy;
+        state.xdr_rate = stable_state.xdr_rate;
+        state.rebuild_balance_index();
+    });
+    cleanup::start_cleanup_timer();
 }
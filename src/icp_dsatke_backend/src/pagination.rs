@@ -0,0 +1,88 @@
+// src/pagination.rs
+//
+// Cloning and returning the whole `users` map from a single query would
+// blow past the 2 MiB query response limit as `users` grows. These
+// queries return bounded slices instead, backed by `BackendState`'s
+// `balance_index` for the sorted cases.
+
+use crate::{PrincipalText, User, STATE};
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk_macros::query;
+
+#[derive(CandidType, Deserialize)]
+pub(crate) struct Page<T> {
+    pub(crate) users: Vec<T>,
+    pub(crate) total: u64,
+}
+
+/// `offset`/`limit` arrive as `u64` over the wire but index a `Vec` sized
+/// by `usize`, which is 32 bits on the wasm32 target canisters run on.
+/// Saturate rather than truncate, so an out-of-range value behaves like
+/// "more than anything we could possibly hold" instead of silently
+/// wrapping around to a small one.
+fn saturating_to_usize(value: u64) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+/// A page of `users`, sorted by `principal_id` so repeated calls with
+/// increasing `offset` see a stable ordering even as the map mutates.
+#[query]
+fn get_users_page(offset: u64, limit: u64) -> Page<User> {
+    let offset = saturating_to_usize(offset);
+    let limit = saturating_to_usize(limit);
+
+    STATE.with(|state| {
+        let state = state.borrow();
+        let mut all: Vec<&User> = state.users.values().collect();
+        all.sort_by(|a, b| a.principal_id.cmp(&b.principal_id));
+
+        let users = all.into_iter().skip(offset).take(limit).cloned().collect();
+
+        Page {
+            users,
+            total: state.users.len() as u64,
+        }
+    })
+}
+
+/// Direct lookup of a single user, for when a caller already knows the
+/// principal and doesn't need a page.
+#[query]
+fn get_user(principal_id: PrincipalText) -> Option<User> {
+    STATE.with(|state| state.borrow().users.get(&principal_id).cloned())
+}
+
+/// The `limit` users with the highest balances, read off `balance_index`
+/// instead of scanning and sorting all of `users`.
+#[query]
+fn get_top_holders(limit: u64) -> Vec<User> {
+    let limit = saturating_to_usize(limit);
+
+    STATE.with(|state| {
+        let state = state.borrow();
+        state
+            .balance_index
+            .iter()
+            .rev()
+            .flat_map(|(_, principals)| principals.iter())
+            .filter_map(|principal| state.users.get(principal).cloned())
+            .take(limit)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_through_values_that_fit_in_usize() {
+        assert_eq!(saturating_to_usize(0), 0);
+        assert_eq!(saturating_to_usize(42), 42);
+    }
+
+    #[test]
+    fn saturates_instead_of_truncating_values_too_big_for_usize() {
+        assert_eq!(saturating_to_usize(u64::MAX), usize::MAX);
+    }
+}
@@ -0,0 +1,140 @@
+// src/xdr_rate.rs
+//
+// ICP -> USD conversion rate for `user_value_usd`, fetched via an HTTPS
+// outcall to a public price feed and cached so we don't pay for a fresh
+// outcall (and a fresh round of replica consensus) on every call.
+
+use crate::{PrincipalText, STATE};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext,
+};
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk_macros::{query, update};
+
+const SOURCE_URL: &str =
+    "https://api.coingecko.com/api/v3/simple/price?ids=internet-computer&vs_currencies=usd";
+const DEFAULT_MIN_REFRESH_INTERVAL_NS: u64 = 5 * 60 * 1_000_000_000;
+
+#[derive(Clone, Copy, CandidType, Deserialize)]
+pub(crate) struct RateCache {
+    pub(crate) usd_rate: f64,
+    pub(crate) fetched_at_ns: u64,
+    pub(crate) min_refresh_interval_ns: u64,
+}
+
+impl Default for RateCache {
+    fn default() -> Self {
+        RateCache {
+            usd_rate: 0.0,
+            fetched_at_ns: 0,
+            min_refresh_interval_ns: DEFAULT_MIN_REFRESH_INTERVAL_NS,
+        }
+    }
+}
+
+/// Replace the minimum interval `refresh_rate` must wait between outcalls.
+#[update]
+fn set_rate_refresh_window(min_refresh_interval_ns: u64) {
+    STATE.with(|state| {
+        state.borrow_mut().xdr_rate.min_refresh_interval_ns = min_refresh_interval_ns;
+    });
+}
+
+/// Fetch the current ICP/USD rate, unless the cached value is still within
+/// the configured refresh window, in which case the cached rate is
+/// returned without making an outcall.
+#[update]
+async fn refresh_rate() -> Result<f64, String> {
+    let cached = STATE.with(|state| state.borrow().xdr_rate);
+    let now_ns = ic_cdk::api::time();
+    if now_ns.saturating_sub(cached.fetched_at_ns) < cached.min_refresh_interval_ns {
+        return Ok(cached.usd_rate);
+    }
+
+    let request = CanisterHttpRequestArgument {
+        url: SOURCE_URL.to_string(),
+        method: HttpMethod::GET,
+        headers: vec![HttpHeader {
+            name: "Accept".to_string(),
+            value: "application/json".to_string(),
+        }],
+        body: None,
+        max_response_bytes: Some(2_048),
+        transform: Some(TransformContext::new(transform_http_response, vec![])),
+    };
+
+    let (response,) = http_request(request)
+        .await
+        .map_err(|(code, msg)| format!("http_request failed ({:?}): {}", code, msg))?;
+
+    let usd_rate = parse_usd_rate(&response.body)?;
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.xdr_rate.usd_rate = usd_rate;
+        state.xdr_rate.fetched_at_ns = now_ns;
+    });
+
+    Ok(usd_rate)
+}
+
+/// The USD value of `principal_id`'s balance at the cached rate, or `None`
+/// if the principal has no account.
+#[query]
+fn user_value_usd(principal_id: PrincipalText) -> Option<f64> {
+    STATE.with(|state| {
+        let state = state.borrow();
+        let balance_e8s = state.users.get(&principal_id)?.balance_e8s;
+        Some((balance_e8s as f64 / 100_000_000.0) * state.xdr_rate.usd_rate)
+    })
+}
+
+/// Strip everything but status and body from the outcall response so every
+/// replica produces an identical result for consensus — response headers
+/// (e.g. `Date`) differ per-replica and would otherwise break it.
+#[query]
+fn transform_http_response(raw: TransformArgs) -> HttpResponse {
+    HttpResponse {
+        status: raw.response.status,
+        body: raw.response.body,
+        headers: vec![],
+    }
+}
+
+fn parse_usd_rate(body: &[u8]) -> Result<f64, String> {
+    let json: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| format!("invalid JSON response: {}", e))?;
+    json["internet-computer"]["usd"]
+        .as_f64()
+        .ok_or_else(|| "response did not contain an internet-computer/usd rate".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_well_formed_response() {
+        let body = br#"{"internet-computer":{"usd":9.87}}"#;
+
+        assert_eq!(parse_usd_rate(body).unwrap(), 9.87);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        let body = b"not json";
+
+        assert!(parse_usd_rate(body).unwrap_err().contains("invalid JSON response"));
+    }
+
+    #[test]
+    fn rejects_json_missing_the_rate_field() {
+        let body = br#"{"internet-computer":{}}"#;
+
+        assert_eq!(
+            parse_usd_rate(body).unwrap_err(),
+            "response did not contain an internet-computer/usd rate"
+        );
+    }
+}